@@ -1,15 +1,20 @@
 mod sdust;
 mod tr;
-use std::io::{self, Write};
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
 
-use crate::tr::find_repeats;
+use crate::tr::{find_repeats, FuzzyMatch};
 use clap::Parser;
 use clio::Input;
-use needletail::parser::SequenceRecord;
 use needletail::{parse_fastx_file, parse_fastx_stdin};
+use std::fmt;
 use std::ops::RangeInclusive;
+use std::path::PathBuf;
 use std::process;
 use std::str::{from_utf8, Utf8Error};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
 
 const FRACTION_RANGE: RangeInclusive<f64> = 0.0..=1.0;
 
@@ -26,7 +31,7 @@ fn fraction_in_range(s: &str) -> Result<f64, String> {
     }
 }
 
-/// Trim terminal repeats from sequences in FASTA files
+/// Trim terminal repeats from sequences in FASTA/FASTQ files
 #[derive(Parser)]
 #[command(version, about, max_term_width = 79)]
 struct Cli {
@@ -66,6 +71,29 @@ struct Cli {
     )]
     min_length: usize,
 
+    /// Minimum fraction of identical bases between the two arms of a terminal
+    /// repeat. When set, repeats are accepted with mismatches instead of
+    /// requiring an exact match. The number of mismatches allowed for an arm
+    /// is rounded up to a whole number, so an accepted arm's identity can be
+    /// somewhat below this fraction
+    #[clap(
+        long,
+        value_parser = fraction_in_range,
+        help_heading = "Terminal repeat identification"
+    )]
+    min_identity: Option<f64>,
+
+    /// Also tolerate insertions/deletions between the two arms of a terminal
+    /// repeat, using a banded alignment (requires --min-identity)
+    #[clap(
+        long,
+        value_parser,
+        default_value = "false",
+        requires = "min_identity",
+        help_heading = "Terminal repeat identification"
+    )]
+    allow_indels: bool,
+
     /// Ignore terminal repeats that contain a high proportion of low complexity
     /// sequences
     #[clap(
@@ -142,35 +170,187 @@ struct Cli {
         help_heading = "Output"
     )]
     disable_trimming: bool,
+
+    /// Number of worker threads used to process records. Output is always
+    /// written in input order
+    #[clap(
+        short = 'j',
+        long,
+        value_parser,
+        default_value = "1",
+        help_heading = "Performance"
+    )]
+    threads: usize,
+
+    /// Output format. `auto` mirrors the input format, trimming and emitting
+    /// FASTQ records (quality scores included) when the input is FASTQ
+    #[clap(long, value_enum, default_value = "auto", help_heading = "Output")]
+    out_format: OutFormat,
+
+    /// Write a table of terminal-repeat coordinates to this path, with one
+    /// row per input record in the default `tsv` format (see
+    /// `--report-format`). Can be combined with `--disable-trimming` to
+    /// detect and report repeats without modifying the sequence output
+    #[clap(long, value_parser, help_heading = "Output")]
+    report: Option<PathBuf>,
+
+    /// Format of the `--report` table: `tsv` writes one row per record;
+    /// `bed` writes a BED interval for each terminal repeat arm
+    #[clap(
+        long,
+        value_enum,
+        default_value = "tsv",
+        requires = "report",
+        help_heading = "Output"
+    )]
+    report_format: ReportFormat,
+}
+
+/// Output sequence format, see [`Cli::out_format`].
+#[derive(Clone, Copy, clap::ValueEnum)]
+enum OutFormat {
+    Fasta,
+    Fastq,
+    Auto,
+}
+
+/// Report table format, see [`Cli::report_format`].
+#[derive(Clone, Copy, clap::ValueEnum)]
+enum ReportFormat {
+    Tsv,
+    Bed,
+}
+
+/// Error formatting a single record, see [`format_record`].
+#[derive(Debug)]
+enum FormatError {
+    Utf8(Utf8Error),
+    /// `--out-format fastq` was requested for `header`, but the record has no
+    /// quality scores (e.g. the input is FASTA).
+    MissingQuality {
+        header: String,
+    },
+}
+
+impl From<Utf8Error> for FormatError {
+    fn from(e: Utf8Error) -> Self {
+        FormatError::Utf8(e)
+    }
+}
+
+impl fmt::Display for FormatError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FormatError::Utf8(e) => write!(f, "{}", e),
+            FormatError::MissingQuality { header } => write!(
+                f,
+                "--out-format fastq requires quality scores, but record '{}' has none (is \
+                 the input FASTA?)",
+                header
+            ),
+        }
+    }
 }
 
 fn format_record(
-    record: &SequenceRecord<'_>,
+    header: &[u8],
     sequence: &[u8],
+    quality: Option<&[u8]>,
     has_dtr: bool,
     has_itr: bool,
     tr_length: usize,
     include_tr_info: bool,
     disable_trimming: bool,
-) -> Result<String, Utf8Error> {
-    let header = from_utf8(record.id())?;
+    out_format: OutFormat,
+) -> Result<String, FormatError> {
+    let header = from_utf8(header)?;
     let sequence = from_utf8(sequence)?;
-    let trimmed_sequence = if (has_dtr || has_itr) & !disable_trimming {
+    let trim = (has_dtr || has_itr) & !disable_trimming;
+    let trimmed_sequence = if trim {
         &sequence[..sequence.len() - tr_length]
     } else {
         sequence
     };
-    let header_line = if include_tr_info {
+    let tr_info = if include_tr_info {
         match (has_dtr, has_itr) {
-            (true, _) => format!(">{} tr=dtr tr_length={}", header, tr_length),
-            (_, true) => format!(">{} tr=itr tr_length={}", header, tr_length),
-            _ => format!(">{} tr=none tr_length=0", header),
+            (true, _) => format!(" tr=dtr tr_length={}", tr_length),
+            (_, true) => format!(" tr=itr tr_length={}", tr_length),
+            _ => " tr=none tr_length=0".to_string(),
+        }
+    } else {
+        String::new()
+    };
+
+    let emit_fastq = match out_format {
+        OutFormat::Fasta => false,
+        OutFormat::Fastq => {
+            if quality.is_none() {
+                return Err(FormatError::MissingQuality {
+                    header: header.to_string(),
+                });
+            }
+            true
         }
+        OutFormat::Auto => quality.is_some(),
+    };
+
+    if emit_fastq {
+        let quality = from_utf8(quality.unwrap())?;
+        let trimmed_quality = if trim {
+            &quality[..quality.len() - tr_length]
+        } else {
+            quality
+        };
+        Ok(format!(
+            "@{}{}\n{}\n+\n{}",
+            header, tr_info, trimmed_sequence, trimmed_quality
+        ))
+    } else {
+        let wrapped_sequence = textwrap::fill(trimmed_sequence, 80);
+        Ok(format!(">{}{}\n{}", header, tr_info, wrapped_sequence))
+    }
+}
+
+/// Build the `--report` row(s) for a single record. Coordinates are
+/// half-open `[start, end)` intervals into the untrimmed sequence. For
+/// `ReportFormat::Bed` no row is produced when no terminal repeat was found,
+/// since BED doesn't have a natural representation for a zero-length feature.
+fn format_report_row(
+    header: &str,
+    seq_len: usize,
+    has_dtr: bool,
+    has_itr: bool,
+    tr_length: usize,
+    tr_length_3prime: usize,
+    report_format: ReportFormat,
+) -> Option<String> {
+    let repeat_type = match (has_dtr, has_itr) {
+        (true, _) => "dtr",
+        (_, true) => "itr",
+        _ => "none",
+    };
+    // `find_repeats` can return a rejected candidate's lengths here (e.g.
+    // when `--ignore-low-complexity`/`--ignore-ambiguous` rule it out), so
+    // only trust them when a repeat was actually reported.
+    let (tr_length, tr_length_3prime) = if repeat_type == "none" {
+        (0, 0)
     } else {
-        format!(">{}", header)
+        (tr_length, tr_length_3prime)
     };
-    let wrapped_sequence = textwrap::fill(trimmed_sequence, 80);
-    Ok(format!("{}\n{}", header_line, wrapped_sequence))
+    // The 3' arm's own length (not `tr_length`, the 5' arm's length) sets its
+    // coordinates: with `--allow-indels` the two arms need not be the same
+    // length.
+    let three_prime_start = seq_len - tr_length_3prime;
+    match report_format {
+        ReportFormat::Tsv => Some(format!(
+            "{header}\t{seq_len}\t{repeat_type}\t{tr_length}\t0\t{tr_length}\t{three_prime_start}\t{seq_len}\n"
+        )),
+        ReportFormat::Bed if tr_length > 0 => Some(format!(
+            "{header}\t0\t{tr_length}\t{repeat_type}_5prime\t{tr_length}\t.\n\
+             {header}\t{three_prime_start}\t{seq_len}\t{repeat_type}_3prime\t{tr_length_3prime}\t.\n"
+        )),
+        ReportFormat::Bed => None,
+    }
 }
 
 fn write_record_to_stdout(record: String) {
@@ -183,6 +363,32 @@ fn write_record_to_stdout(record: String) {
     }
 }
 
+fn write_report_row(writer: &Mutex<BufWriter<File>>, row: &str) {
+    if let Err(e) = writer.lock().unwrap().write_all(row.as_bytes()) {
+        eprintln!("Error writing to report: {}", e);
+    }
+}
+
+/// Buffer `value` under `index` in `pending`, then drain and return every
+/// value whose index forms an unbroken run starting at `*next_index`, in
+/// order. Used by the parallel branch of [`pipeline`] to turn workers'
+/// out-of-order results back into the input's original order, regardless of
+/// which worker finishes first.
+fn drain_in_order<V>(
+    pending: &mut BTreeMap<usize, V>,
+    next_index: &mut usize,
+    index: usize,
+    value: V,
+) -> Vec<V> {
+    pending.insert(index, value);
+    let mut ready = Vec::new();
+    while let Some(v) = pending.remove(next_index) {
+        ready.push(v);
+        *next_index += 1;
+    }
+    ready
+}
+
 fn pipeline(
     input: Input,
     enable_itr_identification: bool,
@@ -195,6 +401,11 @@ fn pipeline(
     exclude_non_tr_seqs: bool,
     include_tr_info: bool,
     disable_trimming: bool,
+    fuzzy: Option<FuzzyMatch>,
+    threads: usize,
+    out_format: OutFormat,
+    report_writer: Option<Arc<Mutex<BufWriter<File>>>>,
+    report_format: ReportFormat,
 ) {
     let reader = match input.is_std() {
         true => parse_fastx_stdin(),
@@ -215,43 +426,199 @@ fn pipeline(
         }
     };
 
-    while let Some(record) = reader.next() {
-        let record = match record {
-            Ok(record) => record,
-            Err(e) => {
-                eprintln!("Error: {}", e);
-                process::exit(1);
+    if threads <= 1 {
+        while let Some(record) = reader.next() {
+            let record = match record {
+                Ok(record) => record,
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    process::exit(1);
+                }
+            };
+
+            let sequence = &record.seq();
+
+            let (has_dtr, has_itr, tr_length, tr_length_3prime) = find_repeats(
+                sequence,
+                min_length,
+                disable_dtr_trimming,
+                enable_itr_identification,
+                ignore_low_complexity,
+                max_low_complexity_frac,
+                ignore_ambiguous,
+                max_ambiguous_frac,
+                fuzzy,
+            );
+
+            if let Some(writer) = &report_writer {
+                match from_utf8(record.id()) {
+                    Ok(header) => {
+                        if let Some(row) = format_report_row(
+                            header,
+                            sequence.len(),
+                            has_dtr,
+                            has_itr,
+                            tr_length,
+                            tr_length_3prime,
+                            report_format,
+                        ) {
+                            write_report_row(writer, &row);
+                        }
+                    }
+                    Err(e) => eprintln!("Error formatting report row: {}", e),
+                }
             }
-        };
 
-        let sequence = &record.seq();
+            if !exclude_non_tr_seqs || has_dtr || has_itr {
+                match format_record(
+                    record.id(),
+                    sequence,
+                    record.qual(),
+                    has_dtr,
+                    has_itr,
+                    tr_length,
+                    include_tr_info,
+                    disable_trimming,
+                    out_format,
+                ) {
+                    Ok(formatted_record) => write_record_to_stdout(formatted_record),
+                    Err(e @ FormatError::MissingQuality { .. }) => {
+                        eprintln!("Error: {}", e);
+                        process::exit(1);
+                    }
+                    Err(e) => eprintln!("Error formatting record: {}", e),
+                };
+            }
+        }
+        return;
+    }
 
-        let (has_dtr, has_itr, tr_length) = find_repeats(
-            sequence,
-            min_length,
-            disable_dtr_trimming,
-            enable_itr_identification,
-            ignore_low_complexity,
-            max_low_complexity_frac,
-            ignore_ambiguous,
-            max_ambiguous_frac,
-        );
+    // needletail's reader isn't `Send`-friendly per record, so the reader stays
+    // on this thread; each record's id/sequence/quality are copied into owned
+    // buffers before being dispatched to the worker pool. Workers report back
+    // out of order, so the writer thread reorders results by index before
+    // emitting.
+    let (work_tx, work_rx) = mpsc::channel::<(usize, Vec<u8>, Vec<u8>, Option<Vec<u8>>)>();
+    let work_rx = Arc::new(Mutex::new(work_rx));
+    let (result_tx, result_rx) = mpsc::channel::<(usize, Option<String>, Option<String>)>();
+    let report_enabled = report_writer.is_some();
 
-        if !exclude_non_tr_seqs || has_dtr || has_itr {
-            match format_record(
-                &record,
-                sequence,
-                has_dtr,
-                has_itr,
-                tr_length,
-                include_tr_info,
-                disable_trimming,
-            ) {
-                Ok(formatted_record) => write_record_to_stdout(formatted_record),
-                Err(e) => eprintln!("Error formatting record: {}", e),
+    thread::scope(|scope| {
+        for _ in 0..threads {
+            let work_rx = Arc::clone(&work_rx);
+            let result_tx = result_tx.clone();
+            scope.spawn(move || loop {
+                let received = work_rx.lock().unwrap().recv();
+                let Ok((index, id, sequence, quality)) = received else {
+                    break;
+                };
+                let (has_dtr, has_itr, tr_length, tr_length_3prime) = find_repeats(
+                    &sequence,
+                    min_length,
+                    disable_dtr_trimming,
+                    enable_itr_identification,
+                    ignore_low_complexity,
+                    max_low_complexity_frac,
+                    ignore_ambiguous,
+                    max_ambiguous_frac,
+                    fuzzy,
+                );
+                let report_row = if report_enabled {
+                    match from_utf8(&id) {
+                        Ok(header) => format_report_row(
+                            header,
+                            sequence.len(),
+                            has_dtr,
+                            has_itr,
+                            tr_length,
+                            tr_length_3prime,
+                            report_format,
+                        ),
+                        Err(e) => {
+                            eprintln!("Error formatting report row: {}", e);
+                            None
+                        }
+                    }
+                } else {
+                    None
+                };
+                let formatted = if !exclude_non_tr_seqs || has_dtr || has_itr {
+                    match format_record(
+                        &id,
+                        &sequence,
+                        quality.as_deref(),
+                        has_dtr,
+                        has_itr,
+                        tr_length,
+                        include_tr_info,
+                        disable_trimming,
+                        out_format,
+                    ) {
+                        Ok(formatted_record) => Some(formatted_record),
+                        Err(e @ FormatError::MissingQuality { .. }) => {
+                            eprintln!("Error: {}", e);
+                            process::exit(1);
+                        }
+                        Err(e) => {
+                            eprintln!("Error formatting record: {}", e);
+                            None
+                        }
+                    }
+                } else {
+                    None
+                };
+                if result_tx.send((index, formatted, report_row)).is_err() {
+                    break;
+                }
+            });
+        }
+        drop(result_tx);
+
+        let writer = scope.spawn(move || {
+            let mut pending = BTreeMap::new();
+            let mut next_index = 0;
+            for (index, formatted, report_row) in result_rx {
+                for (formatted, report_row) in drain_in_order(
+                    &mut pending,
+                    &mut next_index,
+                    index,
+                    (formatted, report_row),
+                ) {
+                    if let Some(formatted_record) = formatted {
+                        write_record_to_stdout(formatted_record);
+                    }
+                    if let (Some(writer), Some(row)) = (&report_writer, report_row) {
+                        write_report_row(writer, &row);
+                    }
+                }
+            }
+        });
+
+        let mut index = 0;
+        while let Some(record) = reader.next() {
+            let record = match record {
+                Ok(record) => record,
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    process::exit(1);
+                }
             };
+            let quality = record.qual().map(|qual| qual.to_vec());
+            let sent = work_tx.send((
+                index,
+                record.id().to_vec(),
+                record.seq().into_owned(),
+                quality,
+            ));
+            index += 1;
+            if sent.is_err() {
+                break;
+            }
         }
-    }
+        drop(work_tx);
+
+        writer.join().unwrap();
+    });
 }
 
 fn main() {
@@ -266,6 +633,32 @@ fn main() {
     let exclude_non_tr_seqs = cli.exclude_non_tr_seqs;
     let include_tr_info = cli.include_tr_info;
     let disable_trimming = cli.disable_trimming;
+    let fuzzy = cli.min_identity.map(|min_identity| FuzzyMatch {
+        min_identity,
+        allow_indels: cli.allow_indels,
+    });
+    let threads = cli.threads;
+    let out_format = cli.out_format;
+    let report_format = cli.report_format;
+    let report_writer = cli.report.map(|path| {
+        let file = match File::create(&path) {
+            Ok(file) => file,
+            Err(e) => {
+                eprintln!("Error: could not create report file: {}", e);
+                process::exit(1);
+            }
+        };
+        let mut writer = BufWriter::new(file);
+        if matches!(report_format, ReportFormat::Tsv) {
+            let header = "seq_id\tseq_length\trepeat_type\trepeat_length\t\
+                five_prime_start\tfive_prime_end\tthree_prime_start\tthree_prime_end\n";
+            if let Err(e) = writer.write_all(header.as_bytes()) {
+                eprintln!("Error writing report header: {}", e);
+                process::exit(1);
+            }
+        }
+        Arc::new(Mutex::new(writer))
+    });
     for input in cli.input {
         pipeline(
             input,
@@ -279,6 +672,216 @@ fn main() {
             exclude_non_tr_seqs,
             include_tr_info,
             disable_trimming,
+            fuzzy,
+            threads,
+            out_format,
+            report_writer.clone(),
+            report_format,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Feed `arrivals` (index, value) pairs through [`drain_in_order`] one at a
+    /// time, as the parallel pipeline's writer thread would as results come in
+    /// off `result_rx`, and collect everything it releases.
+    fn collect_in_order<V: Clone>(arrivals: &[(usize, V)]) -> Vec<V> {
+        let mut pending = BTreeMap::new();
+        let mut next_index = 0;
+        let mut out = Vec::new();
+        for (index, value) in arrivals {
+            out.extend(drain_in_order(
+                &mut pending,
+                &mut next_index,
+                *index,
+                value.clone(),
+            ));
+        }
+        out
+    }
+
+    #[test]
+    fn drain_in_order_matches_input_order_when_results_arrive_in_order() {
+        let arrivals: Vec<(usize, &str)> =
+            (0..5).map(|i| (i, ["a", "b", "c", "d", "e"][i])).collect();
+        assert_eq!(collect_in_order(&arrivals), vec!["a", "b", "c", "d", "e"]);
+    }
+
+    #[test]
+    fn drain_in_order_reconstructs_input_order_from_out_of_order_arrivals() {
+        // Simulates a threaded run where faster workers finish records 1, 3
+        // and 4 before the slower worker finishes records 0 and 2: output must
+        // still match the order a single-threaded run would have produced.
+        let out_of_order = [(1, "b"), (3, "d"), (4, "e"), (0, "a"), (2, "c")];
+        assert_eq!(
+            collect_in_order(&out_of_order),
+            vec!["a", "b", "c", "d", "e"]
+        );
+    }
+
+    #[test]
+    fn drain_in_order_only_releases_an_unbroken_prefix() {
+        let mut pending = BTreeMap::new();
+        let mut next_index = 0;
+        // Record 1 arrives before record 0: nothing can be released yet since
+        // index 0 is still missing.
+        assert!(drain_in_order(&mut pending, &mut next_index, 1, "b").is_empty());
+        // Once record 0 arrives, both 0 and the now-contiguous 1 are released.
+        assert_eq!(
+            drain_in_order(&mut pending, &mut next_index, 0, "a"),
+            vec!["a", "b"]
+        );
+    }
+
+    #[test]
+    fn format_record_fasta_wraps_the_trimmed_sequence() {
+        let record = format_record(
+            b"seq1",
+            b"ACGTACGTAC",
+            None,
+            true,
+            false,
+            4,
+            true,
+            false,
+            OutFormat::Fasta,
+        )
+        .unwrap();
+        assert_eq!(record, ">seq1 tr=dtr tr_length=4\nACGTAC");
+    }
+
+    #[test]
+    fn format_record_fastq_trims_sequence_and_quality_in_step() {
+        let record = format_record(
+            b"seq1",
+            b"ACGTACGTAC",
+            Some(b"IIIIIIIIII"),
+            true,
+            false,
+            4,
+            false,
+            false,
+            OutFormat::Fastq,
+        )
+        .unwrap();
+        assert_eq!(record, "@seq1\nACGTAC\n+\nIIIIII");
+    }
+
+    #[test]
+    fn format_record_auto_emits_fastq_only_when_quality_is_present() {
+        let fasta_input = format_record(
+            b"seq1",
+            b"ACGTACGTAC",
+            None,
+            false,
+            false,
+            0,
+            false,
+            false,
+            OutFormat::Auto,
+        )
+        .unwrap();
+        assert!(fasta_input.starts_with('>'));
+
+        let fastq_input = format_record(
+            b"seq1",
+            b"ACGTACGTAC",
+            Some(b"IIIIIIIIII"),
+            false,
+            false,
+            0,
+            false,
+            false,
+            OutFormat::Auto,
+        )
+        .unwrap();
+        assert!(fastq_input.starts_with('@'));
+    }
+
+    #[test]
+    fn format_record_fastq_on_fasta_input_is_an_error_not_a_silent_fallback() {
+        let err = format_record(
+            b"seq1",
+            b"ACGTACGTAC",
+            None,
+            false,
+            false,
+            0,
+            false,
+            false,
+            OutFormat::Fastq,
+        )
+        .unwrap_err();
+        assert!(matches!(err, FormatError::MissingQuality { ref header } if header == "seq1"));
+    }
+
+    #[test]
+    fn format_record_disable_trimming_keeps_the_full_sequence() {
+        let record = format_record(
+            b"seq1",
+            b"ACGTACGTAC",
+            None,
+            true,
+            false,
+            4,
+            false,
+            true,
+            OutFormat::Fasta,
+        )
+        .unwrap();
+        assert_eq!(record, ">seq1\nACGTACGTAC");
+    }
+
+    #[test]
+    fn format_report_row_tsv_reports_dtr_coordinates() {
+        let row = format_report_row("seq1", 20, true, false, 6, 6, ReportFormat::Tsv).unwrap();
+        assert_eq!(row, "seq1\t20\tdtr\t6\t0\t6\t14\t20\n");
+    }
+
+    #[test]
+    fn format_report_row_tsv_reports_itr_coordinates() {
+        let row = format_report_row("seq1", 20, false, true, 6, 6, ReportFormat::Tsv).unwrap();
+        assert_eq!(row, "seq1\t20\titr\t6\t0\t6\t14\t20\n");
+    }
+
+    #[test]
+    fn format_report_row_tsv_zeroes_out_a_rejected_candidate() {
+        // `find_repeats` can return a rejected candidate's (has_dtr = false,
+        // has_itr = false) lengths unchanged; the row must report `none` with
+        // zeroed-out coordinates, not the rejected candidate's stale length.
+        let row = format_report_row("seq1", 20, false, false, 6, 6, ReportFormat::Tsv).unwrap();
+        assert_eq!(row, "seq1\t20\tnone\t0\t0\t0\t20\t20\n");
+    }
+
+    #[test]
+    fn format_report_row_bed_emits_one_interval_per_arm() {
+        let row = format_report_row("seq1", 20, true, false, 6, 6, ReportFormat::Bed).unwrap();
+        assert_eq!(
+            row,
+            "seq1\t0\t6\tdtr_5prime\t6\t.\nseq1\t14\t20\tdtr_3prime\t6\t.\n"
+        );
+    }
+
+    #[test]
+    fn format_report_row_bed_uses_the_3prime_arms_own_length() {
+        // An `--allow-indels` match where the arms differ in length: the 3'
+        // interval's start and score must follow its own length, not the 5'
+        // arm's length.
+        let row = format_report_row("seq1", 19, true, false, 10, 9, ReportFormat::Bed).unwrap();
+        assert_eq!(
+            row,
+            "seq1\t0\t10\tdtr_5prime\t10\t.\nseq1\t10\t19\tdtr_3prime\t9\t.\n"
+        );
+    }
+
+    #[test]
+    fn format_report_row_bed_omits_a_row_when_no_repeat_was_found() {
+        assert_eq!(
+            format_report_row("seq1", 20, false, false, 0, 0, ReportFormat::Bed),
+            None
         );
     }
 }
@@ -1,37 +1,243 @@
 use crate::sdust::dustmasker;
 use needletail::Sequence;
 
-fn find_dtr(sequence: &[u8], min_length: usize) -> (bool, usize) {
+/// Maximum number of mismatches tolerated between two arms of length `length`
+/// for the arms to still be considered a match at `min_identity`.
+fn max_mismatches(length: usize, min_identity: f64) -> usize {
+    ((length as f64) * (1.0 - min_identity)).ceil() as usize
+}
+
+/// Count mismatches between two equal-length arms, short-circuiting as soon as
+/// `cap` is exceeded. Returns `None` if the arms don't match within `cap`.
+fn count_mismatches(a: &[u8], b: &[u8], cap: usize) -> Option<usize> {
+    let mut mismatches = 0;
+    for (x, y) in a.iter().zip(b.iter()) {
+        if !x.eq_ignore_ascii_case(y) {
+            mismatches += 1;
+            if mismatches > cap {
+                return None;
+            }
+        }
+    }
+    Some(mismatches)
+}
+
+/// Edit distance between `a` and `b`, computed with a Needleman-Wunsch
+/// recursion restricted to a band of `max_edits` around the main diagonal.
+/// Returns `None` once the edit distance is known to exceed `max_edits`
+/// (including when the length difference alone already rules this out).
+fn banded_edit_distance(a: &[u8], b: &[u8], max_edits: usize) -> Option<usize> {
+    let n = a.len();
+    let m = b.len();
+    if n.abs_diff(m) > max_edits {
+        return None;
+    }
+    let mut prev = vec![usize::MAX; m + 1];
+    prev[0] = 0;
+    for (j, slot) in prev.iter_mut().enumerate().skip(1).take(m.min(max_edits)) {
+        *slot = j;
+    }
+    for i in 1..=n {
+        let mut curr = vec![usize::MAX; m + 1];
+        if i <= max_edits {
+            curr[0] = i;
+        }
+        let j_lo = i.saturating_sub(max_edits).max(1);
+        let j_hi = (i + max_edits).min(m);
+        for j in j_lo..=j_hi {
+            let sub_cost = usize::from(!a[i - 1].eq_ignore_ascii_case(&b[j - 1]));
+            let mut best = prev[j - 1].saturating_add(sub_cost);
+            if prev[j] != usize::MAX {
+                best = best.min(prev[j] + 1);
+            }
+            if curr[j - 1] != usize::MAX {
+                best = best.min(curr[j - 1] + 1);
+            }
+            curr[j] = best;
+        }
+        prev = curr;
+    }
+    let distance = prev[m];
+    if distance <= max_edits {
+        Some(distance)
+    } else {
+        None
+    }
+}
+
+/// Identity settings for mismatch-tolerant ("fuzzy") terminal repeat matching.
+#[derive(Clone, Copy)]
+pub struct FuzzyMatch {
+    pub min_identity: f64,
+    pub allow_indels: bool,
+}
+
+/// Whether `prefix` (the candidate terminal arm of length `length`) aligns,
+/// within `max_edits` edits, to some span of `arm_source` anchored at the
+/// opposite end of the sequence. Unlike a fixed-length comparison, the
+/// opposite arm is tried at every length in `[length - max_edits, length +
+/// max_edits]` so that both insertions (opposite arm longer) and deletions
+/// (opposite arm shorter) relative to `length` can be detected, rather than
+/// only ever widening the window in one direction.
+///
+/// `anchor_end` selects which end of `arm_source` the opposite arm is read
+/// from: `true` reads a suffix ending at `arm_source`'s end (direct terminal
+/// repeats, where the opposite arm is the tail of `sequence`); `false` reads
+/// a prefix starting at `arm_source`'s start (inverted terminal repeats,
+/// where the opposite arm is the head of the reverse complement).
+///
+/// Returns the length of the opposite arm that matched, which may differ
+/// from `length` (the `prefix` arm's length) when the match involved an
+/// insertion or deletion — callers that need the opposite arm's actual span
+/// (e.g. to report its coordinates) must use this length, not `length`.
+fn fuzzy_indel_match(
+    prefix: &[u8],
+    arm_source: &[u8],
+    length: usize,
+    max_edits: usize,
+    seq_len: usize,
+    anchor_end: bool,
+) -> Option<usize> {
+    let max_arm_length = seq_len - length;
+    for delta in 0..=max_edits {
+        let candidate_lengths = if delta == 0 {
+            vec![length]
+        } else {
+            vec![length + delta, length.saturating_sub(delta)]
+        };
+        for arm_length in candidate_lengths {
+            if arm_length == 0 || arm_length > max_arm_length {
+                continue;
+            }
+            let arm = if anchor_end {
+                &arm_source[seq_len - arm_length..seq_len]
+            } else {
+                &arm_source[..arm_length]
+            };
+            if banded_edit_distance(prefix, arm, max_edits).is_some() {
+                return Some(arm_length);
+            }
+        }
+    }
+    None
+}
+
+/// KMP prefix function of `sequence`: `pi[i]` is the length of the longest
+/// proper border (prefix that is also a suffix) of `sequence[..=i]`.
+fn prefix_function(sequence: &[u8]) -> Vec<usize> {
+    let n = sequence.len();
+    let mut pi = vec![0; n];
+    for i in 1..n {
+        let mut k = pi[i - 1];
+        while k > 0 && !sequence[i].eq_ignore_ascii_case(&sequence[k]) {
+            k = pi[k - 1];
+        }
+        if sequence[i].eq_ignore_ascii_case(&sequence[k]) {
+            k += 1;
+        }
+        pi[i] = k;
+    }
+    pi
+}
+
+/// Find the longest exact direct terminal repeat via the prefix-function: a
+/// DTR of length `L` is exactly a border of `sequence` (a proper prefix equal
+/// to the equal-length suffix), so every valid length is found by following
+/// failure links from `pi[n-1]` in O(n) instead of comparing each candidate
+/// length's arms directly.
+fn find_dtr_exact(sequence: &[u8], min_length: usize) -> (bool, usize) {
     let seq_len = sequence.len();
-    if seq_len < min_length * 2 {
+    if seq_len == 0 {
         return (false, 0);
     }
-    for length in (min_length..=seq_len / 2).rev() {
-        let start = &sequence[..length];
-        let end = &sequence[seq_len - length..];
-        if start.eq_ignore_ascii_case(end) {
-            return (true, length);
+    let pi = prefix_function(sequence);
+    let mut border = pi[seq_len - 1];
+    while border > 0 {
+        if border >= min_length && border <= seq_len / 2 {
+            return (true, border);
         }
+        border = pi[border - 1];
     }
     (false, 0)
 }
 
-fn find_itr(sequence: &[u8], min_length: usize) -> (bool, usize) {
+/// Returns `(found, five_prime_arm_length, three_prime_arm_length)`. The two
+/// arm lengths only differ when `fuzzy` has `allow_indels` set and the match
+/// involved an insertion/deletion; otherwise the repeat's two arms are the
+/// same length.
+fn find_dtr(sequence: &[u8], min_length: usize, fuzzy: Option<FuzzyMatch>) -> (bool, usize, usize) {
+    let seq_len = sequence.len();
+    if seq_len < min_length * 2 {
+        return (false, 0, 0);
+    }
+    match fuzzy {
+        None => {
+            let (found, length) = find_dtr_exact(sequence, min_length);
+            (found, length, length)
+        }
+        Some(FuzzyMatch {
+            min_identity,
+            allow_indels,
+        }) => {
+            for length in (min_length..=seq_len / 2).rev() {
+                let start = &sequence[..length];
+                let max_edits = max_mismatches(length, min_identity);
+                let matched_arm_length = if allow_indels {
+                    fuzzy_indel_match(start, sequence, length, max_edits, seq_len, true)
+                } else {
+                    let end = &sequence[seq_len - length..];
+                    count_mismatches(start, end, max_edits).map(|_| length)
+                };
+                if let Some(arm_length) = matched_arm_length {
+                    return (true, length, arm_length);
+                }
+            }
+            (false, 0, 0)
+        }
+    }
+}
+
+/// Returns `(found, five_prime_arm_length, three_prime_arm_length)`, see
+/// [`find_dtr`].
+fn find_itr(sequence: &[u8], min_length: usize, fuzzy: Option<FuzzyMatch>) -> (bool, usize, usize) {
     let seq_len = sequence.len();
     let rev_complement = sequence.reverse_complement();
     if seq_len < min_length * 2 {
-        return (false, 0);
+        return (false, 0, 0);
     }
-    let start = &sequence[..min_length];
-    let end = &rev_complement[..min_length];
-    if start.eq(end) {
-        let mut i = min_length;
-        while i <= seq_len / 2 && sequence[..i].eq_ignore_ascii_case(&rev_complement[..i]) {
-            i += 1;
+    match fuzzy {
+        None => {
+            let start = &sequence[..min_length];
+            let end = &rev_complement[..min_length];
+            if start.eq(end) {
+                let mut i = min_length;
+                while i <= seq_len / 2 && sequence[..i].eq_ignore_ascii_case(&rev_complement[..i]) {
+                    i += 1;
+                }
+                (true, i - 1, i - 1)
+            } else {
+                (false, 0, 0)
+            }
+        }
+        Some(FuzzyMatch {
+            min_identity,
+            allow_indels,
+        }) => {
+            for length in (min_length..=seq_len / 2).rev() {
+                let start = &sequence[..length];
+                let max_edits = max_mismatches(length, min_identity);
+                let matched_arm_length = if allow_indels {
+                    fuzzy_indel_match(start, &rev_complement, length, max_edits, seq_len, false)
+                } else {
+                    let end = &rev_complement[..length];
+                    count_mismatches(start, end, max_edits).map(|_| length)
+                };
+                if let Some(arm_length) = matched_arm_length {
+                    return (true, length, arm_length);
+                }
+            }
+            (false, 0, 0)
         }
-        (true, i - 1)
-    } else {
-        (false, 0)
     }
 }
 
@@ -57,6 +263,12 @@ fn evaluate_ambiguous_bases(sequence: &[u8], tr_length: usize, max_ambig_frac: f
     (n_ambig as f64) / (tr_length as f64) <= max_ambig_frac
 }
 
+/// Returns `(has_dtr, has_itr, tr_length, tr_length_3prime)`. `tr_length` is
+/// the repeat's 5′-arm length, used for trimming and header annotation.
+/// `tr_length_3prime` is the 3′-arm length, which only differs from
+/// `tr_length` for an `--allow-indels` match spanning an insertion/deletion;
+/// callers that report the 3′ arm's coordinates must use it instead of
+/// `tr_length`.
 pub fn find_repeats(
     sequence: &[u8],
     min_length: usize,
@@ -66,40 +278,137 @@ pub fn find_repeats(
     max_low_complexity_frac: f64,
     ignore_ambiguous: bool,
     max_ambiguous_frac: f64,
-) -> (bool, bool, usize) {
+    fuzzy: Option<FuzzyMatch>,
+) -> (bool, bool, usize, usize) {
     if !disable_dtr_identification {
-        let (has_dtr, tr_length) = find_dtr(sequence, min_length);
+        let (has_dtr, tr_length, tr_length_3prime) = find_dtr(sequence, min_length, fuzzy);
         if has_dtr || !enable_itr_identification {
             if ignore_low_complexity {
                 match evaluate_tr_complexity(sequence, tr_length, max_low_complexity_frac) {
-                    true => return (has_dtr, false, tr_length),
-                    false => return (false, false, tr_length),
+                    true => return (has_dtr, false, tr_length, tr_length_3prime),
+                    false => return (false, false, tr_length, tr_length_3prime),
                 }
             }
             if ignore_ambiguous {
                 match evaluate_ambiguous_bases(sequence, tr_length, max_ambiguous_frac) {
-                    true => return (has_dtr, false, tr_length),
-                    false => return (false, false, tr_length),
+                    true => return (has_dtr, false, tr_length, tr_length_3prime),
+                    false => return (false, false, tr_length, tr_length_3prime),
                 }
             }
-            return (has_dtr, false, tr_length);
+            return (has_dtr, false, tr_length, tr_length_3prime);
         }
     }
     if enable_itr_identification {
-        let (has_itr, tr_length) = find_itr(sequence, min_length);
+        let (has_itr, tr_length, tr_length_3prime) = find_itr(sequence, min_length, fuzzy);
         if ignore_low_complexity {
             match evaluate_tr_complexity(sequence, tr_length, max_low_complexity_frac) {
-                true => return (false, has_itr, tr_length),
-                false => return (false, false, tr_length),
+                true => return (false, has_itr, tr_length, tr_length_3prime),
+                false => return (false, false, tr_length, tr_length_3prime),
             }
         }
         if ignore_ambiguous {
             match evaluate_ambiguous_bases(sequence, tr_length, max_ambiguous_frac) {
-                true => return (false, has_itr, tr_length),
-                false => return (false, false, tr_length),
+                true => return (false, has_itr, tr_length, tr_length_3prime),
+                false => return (false, false, tr_length, tr_length_3prime),
             }
         }
-        return (false, has_itr, tr_length);
+        return (false, has_itr, tr_length, tr_length_3prime);
+    }
+    (false, false, 0, 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prefix_function_enumerates_borders_longest_first() {
+        // "AAAAAA" has borders of every length from 5 down to 1.
+        let pi = prefix_function(b"AAAAAA");
+        assert_eq!(pi, vec![0, 1, 2, 3, 4, 5]);
+    }
+
+    #[test]
+    fn find_dtr_exact_picks_longest_border_within_min_length() {
+        // Longest border of "ACGTACGT" is "ACGT" (length 4).
+        assert_eq!(find_dtr_exact(b"ACGTACGT", 3), (true, 4));
+        // With "AAAAAA" the longest border at or below seq_len / 2 is 3.
+        assert_eq!(find_dtr_exact(b"AAAAAA", 3), (true, 3));
+        // No border reaches the requested minimum length.
+        assert_eq!(find_dtr_exact(b"ACGTACGT", 5), (false, 0));
+    }
+
+    #[test]
+    fn find_dtr_exact_does_not_panic_on_empty_sequence() {
+        assert_eq!(find_dtr_exact(b"", 1), (false, 0));
+    }
+
+    #[test]
+    fn find_dtr_tolerates_a_mismatch_within_min_identity() {
+        // Arms "ACGTT" / "ACGTA" differ by a single mismatch, which is within
+        // the one mismatch that min_identity = 0.8 allows for length 5.
+        let sequence = b"ACGTTACGTA";
+        let fuzzy = Some(FuzzyMatch {
+            min_identity: 0.8,
+            allow_indels: false,
+        });
+        assert_eq!(find_dtr(sequence, 5, fuzzy), (true, 5, 5));
+    }
+
+    #[test]
+    fn fuzzy_indel_match_detects_a_deletion_in_the_opposite_arm() {
+        // The opposite arm is the prefix arm with one base deleted, so the two
+        // arms only align if the band search also tries shorter candidate
+        // lengths, not just longer ones (regression for the one-directional
+        // window bug). The returned arm length must reflect the shorter,
+        // actually-matched arm, not the prefix's length.
+        let prefix = b"ACGTACGTAC";
+        let arm_with_deletion = b"ACGTCGTAC";
+        let mut sequence = prefix.to_vec();
+        sequence.extend_from_slice(arm_with_deletion);
+
+        assert_eq!(
+            fuzzy_indel_match(prefix, &sequence, prefix.len(), 1, sequence.len(), true),
+            Some(arm_with_deletion.len()),
+        );
+    }
+
+    #[test]
+    fn fuzzy_indel_match_rejects_unrelated_sequences() {
+        let prefix = b"ACGTACGTAC";
+        let sequence = b"ACGTACGTACTTTTTTTTT";
+        assert_eq!(
+            fuzzy_indel_match(prefix, sequence, prefix.len(), 1, sequence.len(), true),
+            None,
+        );
+    }
+
+    #[test]
+    fn banded_edit_distance_rejects_beyond_max_edits() {
+        assert_eq!(banded_edit_distance(b"ACGT", b"ACGT", 1), Some(0));
+        assert_eq!(banded_edit_distance(b"ACGT", b"ACGA", 1), Some(1));
+        assert_eq!(banded_edit_distance(b"ACGT", b"TGCA", 1), None);
+    }
+
+    #[test]
+    fn find_dtr_reports_the_3prime_arms_own_length_when_it_differs() {
+        // The 3' arm is the 5' arm with one base deleted, so the repeat's two
+        // arms are not the same length; callers that need the 3' arm's real
+        // span (e.g. `--report` coordinates) must get 9 back, not the 5'
+        // arm's length of 10. A spacer base separates the two arms so the
+        // sequence is long enough for length 10 to be tried (<= seq_len / 2).
+        let five_prime_arm = b"ACGTACGTAC";
+        let three_prime_arm = b"ACGTCGTAC";
+        let mut sequence = five_prime_arm.to_vec();
+        sequence.push(b'N');
+        sequence.extend_from_slice(three_prime_arm);
+        let fuzzy = Some(FuzzyMatch {
+            min_identity: 0.9,
+            allow_indels: true,
+        });
+        assert_eq!(
+            find_dtr(&sequence, 5, fuzzy),
+            (true, five_prime_arm.len(), three_prime_arm.len()),
+        );
     }
-    (false, false, 0)
 }